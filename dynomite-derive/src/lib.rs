@@ -34,11 +34,13 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::Span;
-use quote::{quote, ToTokens};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
 use syn::{
+    parse_quote,
     Data::{Enum, Struct},
-    DataStruct, DeriveInput, Field, Fields, Ident, Meta, Variant, Visibility,
+    Attribute, DataStruct, DeriveInput, Field, Fields, Ident, Lit, Meta, NestedMeta, Type,
+    Variant, Visibility,
 };
 
 /// Derives `dynomite::Item` type for struts with named fields
@@ -47,99 +49,244 @@ use syn::{
 ///
 /// * `#[hash]` - required attribute, expected to be applied the target [hash attribute](https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/HowItWorks.CoreComponents.html#HowItWorks.CoreComponents.PrimaryKey) field with an derivable DynamoDB attribute value of String, Number or Binary
 /// * `#[range]` - optional attribute, may be applied to one target [range attribute](https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/HowItWorks.CoreComponents.html#HowItWorks.CoreComponents.SecondaryIndexes) field with an derivable DynamoDB attribute value of String, Number or Binary
+/// * `#[dynomite(rename = "...")]` - store/look up this field under a different attribute key
+/// * `#[dynomite(default)]` - fall back to `Default::default()` when the attribute is absent instead of failing
+/// * `#[dynomite(skip)]` - omit this field when serializing; requires `#[dynomite(default)]`
+/// * `#[dynomite(flatten)]` - inline a nested struct's attributes into this struct's map
+/// * `#[gsi(name = "...", hash)]` / `#[gsi(name = "...", range)]` - marks this field as the hash
+///   or range key of a [global secondary index](https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/GSI.html)
+///   named `name`; dynomite derives a standalone `{Name}{Index}Key` `Item` (e.g. `PersonByEmailKey`)
+///   for looking items up by it
+/// * `#[lsi(name = "...", hash)]` / `#[lsi(name = "...", range)]` - same as `#[gsi]`, for a
+///   [local secondary index](https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/LSI.html)
 ///
-/// # Panics
+/// `Option<T>` fields are sparse out of the box, with no attribute argument required:
+/// a present attribute deserializes into `Some(..)`, an absent one into `None`, and a
+/// `None` value is never written out as an attribute.
 ///
-/// This proc macro will panic when applied to other types
-#[proc_macro_derive(Item, attributes(hash, range))]
+/// Misuse (applying this derive to the wrong kind of type, or duplicating
+/// `#[hash]`/`#[range]`) is reported as a compile error pointing at the
+/// offending item, rather than a macro panic.
+#[proc_macro_derive(Item, attributes(hash, range, dynomite, gsi, lsi))]
 pub fn derive_item(input: TokenStream) -> TokenStream {
-    let ast = syn::parse_macro_input!(input);
-    let gen = expand_item(ast);
-    gen.into_token_stream().into()
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    expand_item(ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 /// Derives `dynomite::Attribute` for enum types
 ///
-/// # Panics
-///
-/// This proc macro will panic when applied to other types
+/// Misuse (applying this derive to a non-enum type) is reported as a
+/// compile error pointing at the offending item, rather than a macro panic.
 #[proc_macro_derive(Attribute)]
 pub fn derive_attribute(input: TokenStream) -> TokenStream {
-    let ast = syn::parse_macro_input!(input);
-    let gen = expand_attribute(ast);
-    gen.into_token_stream().into()
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    expand_attribute(ast)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
-fn expand_attribute(ast: DeriveInput) -> impl ToTokens {
+fn expand_attribute(ast: DeriveInput) -> syn::Result<TokenStream2> {
     let name = &ast.ident;
     match ast.data {
         Enum(variants) => {
             make_dynomite_attr(name, &variants.variants.into_iter().collect::<Vec<_>>())
         }
-        _ => panic!("Dynomite Attributes can only be generated for enum types"),
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "Dynomite Attributes can only be generated for enum types",
+        )),
     }
 }
 
 /// impl ::dynomite::Attribute for Name {
 ///   fn into_attr(self) -> ::dynomite::dynamodb::AttributeValue {
-///     let arm = match self {
-///        Name::Variant => "Variant".to_string()
-///     };
-///     ::dynomite::dynamodb::AttributeValue {
-///        s: Some(arm),
-///        ..Default::default()
+///     match self {
+///        // unit variants round-trip through `s`, same as before
+///        Name::Unit => ::dynomite::dynamodb::AttributeValue {
+///           s: Some(stringify!(Unit).to_string()),
+///           ..Default::default()
+///        },
+///        // data-carrying variants are tagged maps: { "Variant": <encoded data> }
+///        Name::Newtype(inner) => {
+///           let mut m = ::std::collections::HashMap::new();
+///           m.insert(stringify!(Newtype).to_string(), ::dynomite::Attribute::into_attr(inner));
+///           ::dynomite::dynamodb::AttributeValue { m: Some(m), ..Default::default() }
+///        }
 ///     }
 ///   }
 ///   fn from_attr(value: ::dynomite::dynamodb::AttributeValue) -> Result<Self, ::dynomite::AttributeError> {
-///     value.s.ok_or(::dynomite::AttributeError::InvalidType)
-///       .and_then(|value| match &value[..] {
-///          "Variant" => Ok(Name::Variant),
-///          _ => Err(::dynomite::AttributeError::InvalidFormat)
-///       })
+///     if let Some(tag) = value.s {
+///        return match &tag[..] {
+///           "Unit" => Ok(Name::Unit),
+///           _ => Err(::dynomite::AttributeError::InvalidFormat),
+///        };
+///     }
+///     let mut map = value.m.ok_or(::dynomite::AttributeError::InvalidType)?;
+///     if map.len() != 1 {
+///        return Err(::dynomite::AttributeError::InvalidFormat);
+///     }
+///     let (tag, value) = map.drain().next().expect("map has exactly one entry");
+///     match &tag[..] {
+///        "Newtype" => Ok(Name::Newtype(::dynomite::Attribute::from_attr(value)?)),
+///        _ => Err(::dynomite::AttributeError::InvalidFormat),
+///     }
 ///   }
 /// }
 fn make_dynomite_attr(
     name: &Ident,
     variants: &[Variant],
-) -> impl ToTokens {
+) -> syn::Result<TokenStream2> {
     let attr = quote!(::dynomite::Attribute);
     let err = quote!(::dynomite::AttributeError);
-    let into_match_arms = variants.iter().map(|var| {
-        let vname = &var.ident;
-        quote! {
-            #name::#vname => stringify!(#vname).to_string(),
+
+    let into_match_arms = variants
+        .iter()
+        .map(|var| {
+            let vname = &var.ident;
+            match &var.fields {
+                Fields::Unit => Ok(quote! {
+                    #name::#vname => ::dynomite::dynamodb::AttributeValue {
+                        s: Some(stringify!(#vname).to_string()),
+                        ..Default::default()
+                    },
+                }),
+                Fields::Unnamed(unnamed) => {
+                    if unnamed.unnamed.len() != 1 {
+                        return Err(syn::Error::new_spanned(
+                            unnamed,
+                            "dynomite Attribute derive only supports newtype variants with a single field",
+                        ));
+                    }
+                    Ok(quote! {
+                        #name::#vname(inner) => {
+                            let mut m = ::std::collections::HashMap::new();
+                            m.insert(
+                                stringify!(#vname).to_string(),
+                                ::dynomite::Attribute::into_attr(inner),
+                            );
+                            ::dynomite::dynamodb::AttributeValue {
+                                m: Some(m),
+                                ..Default::default()
+                            }
+                        },
+                    })
+                }
+                Fields::Named(named) => {
+                    let field_names = named
+                        .named
+                        .iter()
+                        .map(|field| field.ident.as_ref().unwrap())
+                        .collect::<Vec<_>>();
+                    let field_inserts = field_names
+                        .iter()
+                        .map(|fname| {
+                            quote! {
+                                fields.insert(
+                                    stringify!(#fname).to_string(),
+                                    ::dynomite::Attribute::into_attr(#fname),
+                                );
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    Ok(quote! {
+                        #name::#vname { #(#field_names),* } => {
+                            let mut fields = ::std::collections::HashMap::new();
+                            #(#field_inserts)*
+                            let mut m = ::std::collections::HashMap::new();
+                            m.insert(
+                                stringify!(#vname).to_string(),
+                                ::dynomite::dynamodb::AttributeValue {
+                                    m: Some(fields),
+                                    ..Default::default()
+                                },
+                            );
+                            ::dynomite::dynamodb::AttributeValue {
+                                m: Some(m),
+                                ..Default::default()
+                            }
+                        },
+                    })
+                }
+            }
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let unit_from_arms = variants.iter().filter_map(|var| match &var.fields {
+        Fields::Unit => {
+            let vname = &var.ident;
+            Some(quote! {
+                stringify!(#vname) => Ok(#name::#vname),
+            })
         }
+        _ => None,
     });
-    let from_match_arms = variants.iter().map(|var| {
+
+    let data_from_arms = variants.iter().filter_map(|var| {
         let vname = &var.ident;
-        quote! {
-            stringify!(#vname) => Ok(#name::#vname),
+        match &var.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(_) => Some(quote! {
+                stringify!(#vname) => Ok(#name::#vname(::dynomite::Attribute::from_attr(value)?)),
+            }),
+            Fields::Named(named) => {
+                let field_conversions = named
+                    .named
+                    .iter()
+                    .map(|field| {
+                        let fname = field.ident.as_ref().unwrap();
+                        quote! {
+                            #fname: ::dynomite::Attribute::from_attr(
+                                fields.remove(stringify!(#fname))
+                                    .ok_or(::dynomite::AttributeError::MissingField {
+                                        name: stringify!(#fname).to_string(),
+                                    })?
+                            )?
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                Some(quote! {
+                    stringify!(#vname) => {
+                        let mut fields = value.m.ok_or(::dynomite::AttributeError::InvalidType)?;
+                        Ok(#name::#vname {
+                            #(#field_conversions),*
+                        })
+                    },
+                })
+            }
         }
     });
 
-    quote! {
+    Ok(quote! {
         impl #attr for #name {
             fn into_attr(self) -> ::dynomite::dynamodb::AttributeValue {
-                let arm = match self {
+                match self {
                     #(#into_match_arms)*
-                };
-                ::dynomite::dynamodb::AttributeValue {
-                    s: Some(arm),
-                    ..Default::default()
                 }
             }
             fn from_attr(value: ::dynomite::dynamodb::AttributeValue) -> Result<Self, #err> {
-                value.s.ok_or(::dynomite::AttributeError::InvalidType)
-                    .and_then(|value| match &value[..] {
-                        #(#from_match_arms)*
-                        _ => Err(::dynomite::AttributeError::InvalidFormat)
-                    })
+                if let Some(tag) = value.s {
+                    return match &tag[..] {
+                        #(#unit_from_arms)*
+                        _ => Err(::dynomite::AttributeError::InvalidFormat),
+                    };
+                }
+                let mut map = value.m.ok_or(::dynomite::AttributeError::InvalidType)?;
+                if map.len() != 1 {
+                    return Err(::dynomite::AttributeError::InvalidFormat);
+                }
+                let (tag, value) = map.drain().next().expect("map has exactly one entry");
+                match &tag[..] {
+                    #(#data_from_arms)*
+                    _ => Err(::dynomite::AttributeError::InvalidFormat),
+                }
             }
         }
-    }
+    })
 }
 
-fn expand_item(ast: DeriveInput) -> impl ToTokens {
+fn expand_item(ast: DeriveInput) -> syn::Result<TokenStream2> {
     let name = &ast.ident;
     let vis = &ast.vis;
     match ast.data {
@@ -147,9 +294,15 @@ fn expand_item(ast: DeriveInput) -> impl ToTokens {
             Fields::Named(named) => {
                 make_dynomite_item(vis, name, &named.named.into_iter().collect::<Vec<_>>())
             }
-            _ => panic!("Dynomite Items require named fields"),
+            other => Err(syn::Error::new_spanned(
+                other,
+                "Dynomite Items require named fields",
+            )),
         },
-        _ => panic!("Dynomite Items can only be generated for structs"),
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "Dynomite Items can only be generated for structs",
+        )),
     }
 }
 
@@ -157,56 +310,196 @@ fn make_dynomite_item(
     vis: &Visibility,
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
-    let dynamodb_traits = get_dynomite_item_traits(vis, name, fields);
-    let from_attribute_map = get_from_attributes_trait(name, fields);
-    let to_attribute_map = get_to_attribute_map_trait(name, fields);
+) -> syn::Result<TokenStream2> {
+    let dynamodb_traits = get_dynomite_item_traits(vis, name, fields)?;
+    let from_attribute_map = get_from_attributes_trait(name, fields)?;
+    let to_attribute_map = get_to_attribute_map_trait(name, fields)?;
 
-    quote! {
+    Ok(quote! {
         #from_attribute_map
         #to_attribute_map
         #dynamodb_traits
+    })
+}
+
+/// Per-field `#[dynomite(...)]` customization.
+///
+/// * `rename = "..."` - store/look up the field under a different attribute key
+/// * `default` - fall back to `Default::default()` when the attribute is absent
+/// * `flatten` - inline a nested struct's attributes into the parent map
+/// * `skip` - omit the field entirely when serializing (requires `default`)
+#[derive(Default, Debug)]
+struct FieldOptions {
+    rename: Option<String>,
+    default: bool,
+    flatten: bool,
+    skip: bool,
+}
+
+fn field_options(field: &Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in &field.attrs {
+        let meta = match attr.parse_meta() {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+        let is_dynomite = match &meta {
+            Meta::Word(ident) => ident == "dynomite",
+            Meta::List(list) => list.ident == "dynomite",
+            Meta::NameValue(nv) => nv.ident == "dynomite",
+        };
+        if !is_dynomite {
+            continue;
+        }
+        let list = match meta {
+            Meta::List(list) => list,
+            other => {
+                return Err(syn::Error::new_spanned(
+                    &other,
+                    "expected #[dynomite(...)] attribute list",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.ident == "rename" => match &nv.lit {
+                    Lit::Str(lit) => options.rename = Some(lit.value()),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &nv.lit,
+                            "#[dynomite(rename = \"...\")] expects a string literal",
+                        ))
+                    }
+                },
+                NestedMeta::Meta(Meta::Word(ident)) if ident == "default" => {
+                    options.default = true;
+                }
+                NestedMeta::Meta(Meta::Word(ident)) if ident == "flatten" => {
+                    options.flatten = true;
+                }
+                NestedMeta::Meta(Meta::Word(ident)) if ident == "skip" => {
+                    options.skip = true;
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        &other,
+                        "unrecognized #[dynomite(...)] field attribute",
+                    ))
+                }
+            }
+        }
+    }
+
+    if options.skip && !options.default {
+        return Err(syn::Error::new_spanned(
+            field,
+            "#[dynomite(skip)] requires #[dynomite(default)]",
+        ));
+    }
+
+    if options.skip && options.flatten {
+        return Err(syn::Error::new_spanned(
+            field,
+            "#[dynomite(skip)] and #[dynomite(flatten)] can't be combined: \
+             skip omits the field entirely, flatten reads it from the rest of the map",
+        ));
+    }
+
+    Ok(options)
+}
+
+/// tokens for the string key a field is stored/looked up under, honoring `rename`
+fn field_key_tokens(
+    field_name: &Option<Ident>,
+    options: &FieldOptions,
+) -> TokenStream2 {
+    match &options.rename {
+        Some(rename) => quote!(#rename),
+        None => quote!(stringify!(#field_name)),
+    }
+}
+
+/// whether a field's declared type is `Option<_>`, in which case the
+/// attribute is genuinely optional rather than required
+fn is_option_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => type_path
+            .path
+            .segments
+            .iter()
+            .last()
+            .map(|segment| segment.ident == "Option")
+            .unwrap_or(false),
+        _ => false,
     }
 }
 
 fn get_to_attribute_map_trait(
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
+) -> syn::Result<TokenStream2> {
     let attributes = quote!(::dynomite::Attributes);
     let from = quote!(::std::convert::From);
-    let to_attribute_map = get_to_attribute_map_function(name, fields);
+    let to_attribute_map = get_to_attribute_map_function(name, fields)?;
 
-    quote! {
+    Ok(quote! {
         impl #from<#name> for #attributes {
             #to_attribute_map
         }
-    }
+    })
 }
 
 fn get_to_attribute_map_function(
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
+) -> syn::Result<TokenStream2> {
     let to_attribute_value = quote!(::dynomite::Attribute::into_attr);
 
-    let field_conversions = fields.iter().map(|field| {
-        let field_name = &field.ident;
-        quote! {
-            values.insert(
-                stringify!(#field_name).to_string(),
-                #to_attribute_value(item.#field_name)
-            );
-        }
-    });
+    let field_conversions = fields
+        .iter()
+        .map(|field| {
+            let options = field_options(field)?;
+            let field_name = &field.ident;
+
+            if options.skip {
+                return Ok(quote!());
+            }
+
+            if options.flatten {
+                return Ok(quote! {
+                    values.extend(::dynomite::Attributes::from(item.#field_name));
+                });
+            }
+
+            let key = field_key_tokens(field_name, &options);
+
+            if is_option_type(&field.ty) {
+                // sparse: an absent value is simply never inserted, rather than
+                // written out as a null attribute
+                return Ok(quote! {
+                    if let ::std::option::Option::Some(value) = item.#field_name {
+                        values.insert(#key.to_string(), #to_attribute_value(value));
+                    }
+                });
+            }
 
-    quote! {
+            Ok(quote! {
+                values.insert(
+                    #key.to_string(),
+                    #to_attribute_value(item.#field_name)
+                );
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
         fn from(item: #name) -> Self {
             let mut values = Self::new();
             #(#field_conversions)*
             values
         }
-    }
+    })
 }
 
 ///
@@ -222,64 +515,108 @@ fn get_to_attribute_map_function(
 fn get_from_attributes_trait(
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
+) -> syn::Result<TokenStream2> {
     let from_attrs = quote!(::dynomite::FromAttributes);
-    let from_attribute_map = get_from_attributes_function(fields);
+    let from_attribute_map = get_from_attributes_function(fields)?;
 
-    quote! {
+    Ok(quote! {
         impl #from_attrs for #name {
             #from_attribute_map
         }
-    }
+    })
 }
 
-fn get_from_attributes_function(fields: &[Field]) -> impl ToTokens {
+fn get_from_attributes_function(fields: &[Field]) -> syn::Result<TokenStream2> {
     let attributes = quote!(::dynomite::Attributes);
     let from_attribute_value = quote!(::dynomite::Attribute::from_attr);
     let err = quote!(::dynomite::AttributeError);
-    let field_conversions = fields.iter().map(|field| {
+
+    // flattened fields consume whatever is left in the map, so they're
+    // deserialized after every other field has removed its own key
+    let mut plain_conversions = Vec::new();
+    let mut flatten_conversions = Vec::new();
+
+    for field in fields {
+        let options = field_options(field)?;
         let field_name = &field.ident;
-        quote! {
-            #field_name: #from_attribute_value(
-                attrs.remove(stringify!(#field_name))
-                    .ok_or(::dynomite::AttributeError::MissingField { name: stringify!(#field_name).to_string() })?
-            )?
+
+        if options.flatten {
+            flatten_conversions.push(quote! {
+                #field_name: ::dynomite::FromAttributes::from_attrs(attrs.clone())?
+            });
+            continue;
         }
-    });
 
-    quote! {
+        if options.skip {
+            plain_conversions.push(quote! {
+                #field_name: ::std::default::Default::default()
+            });
+            continue;
+        }
+
+        let key = field_key_tokens(field_name, &options);
+        if is_option_type(&field.ty) {
+            // an absent sparse attribute is `None`, not a missing-field error
+            plain_conversions.push(quote! {
+                #field_name: match attrs.remove(#key) {
+                    ::std::option::Option::Some(value) => ::std::option::Option::Some(#from_attribute_value(value)?),
+                    ::std::option::Option::None => ::std::option::Option::None,
+                }
+            });
+        } else if options.default {
+            plain_conversions.push(quote! {
+                #field_name: match attrs.remove(#key) {
+                    ::std::option::Option::Some(value) => #from_attribute_value(value)?,
+                    ::std::option::Option::None => ::std::default::Default::default(),
+                }
+            });
+        } else {
+            plain_conversions.push(quote! {
+                #field_name: #from_attribute_value(
+                    attrs.remove(#key)
+                        .ok_or(::dynomite::AttributeError::MissingField { name: #key.to_string() })?
+                )?
+            });
+        }
+    }
+
+    let field_conversions = plain_conversions.into_iter().chain(flatten_conversions);
+
+    Ok(quote! {
         fn from_attrs(mut attrs: #attributes) -> Result<Self, #err> {
             Ok(Self {
                 #(#field_conversions),*
             })
         }
-    }
+    })
 }
 
 fn get_dynomite_item_traits(
     vis: &Visibility,
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
-    let impls = get_item_impls(vis, name, fields);
+) -> syn::Result<TokenStream2> {
+    let impls = get_item_impls(vis, name, fields)?;
 
-    quote! {
+    Ok(quote! {
         #impls
-    }
+    })
 }
 
 fn get_item_impls(
     vis: &Visibility,
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
-    let item_trait = get_item_trait(name, fields);
-    let key_struct = get_key_struct(vis, name, fields);
+) -> syn::Result<TokenStream2> {
+    let item_trait = get_item_trait(name, fields)?;
+    let key_struct = get_key_struct(vis, name, fields)?;
+    let index_key_structs = get_index_key_structs(vis, name, fields)?;
 
-    quote! {
+    Ok(quote! {
         #item_trait
         #key_struct
-    }
+        #index_key_structs
+    })
 }
 
 ///
@@ -294,18 +631,26 @@ fn get_item_impls(
 fn get_item_trait(
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
+) -> syn::Result<TokenStream2> {
     let item = quote!(::dynomite::Item);
     let attribute_map = quote!(
         ::std::collections::HashMap<String, ::dynomite::dynamodb::AttributeValue>
     );
-    let hash_key_name = field_name_with_attribute(&fields, "hash");
-    let range_key_name = field_name_with_attribute(&fields, "range");
+    let hash_key = field_with_attribute(fields, "hash")?;
+    let range_key = field_with_attribute(fields, "range")?;
 
-    let hash_key_insert = get_key_inserter(&hash_key_name);
-    let range_key_insert = get_key_inserter(&range_key_name);
+    let hash_key_insert = hash_key
+        .as_ref()
+        .map(get_key_inserter)
+        .transpose()?
+        .unwrap_or(quote!());
+    let range_key_insert = range_key
+        .as_ref()
+        .map(get_key_inserter)
+        .transpose()?
+        .unwrap_or(quote!());
 
-    hash_key_name
+    Ok(hash_key
         .map(|_| {
             quote! {
                 impl #item for #name {
@@ -318,56 +663,68 @@ fn get_item_trait(
                 }
             }
         })
-        .unwrap_or(quote! {})
-}
-
-fn field_name_with_attribute(
-    fields: &[Field],
-    attribute_name: &str,
-) -> Option<Ident> {
-    field_with_attribute(fields, attribute_name).map(|field| {
-        field.ident.unwrap_or_else(|| {
-            panic!(
-                "should have an identifier with an {} attribute",
-                attribute_name
-            )
-        })
-    })
+        .unwrap_or(quote! {}))
 }
 
 fn field_with_attribute(
     fields: &[Field],
     attribute_name: &str,
-) -> Option<Field> {
-    let mut fields = fields.iter().cloned().filter(|field| {
+) -> syn::Result<Option<Field>> {
+    let mut matches = fields.iter().cloned().filter(|field| {
         field.attrs.iter().any(|attr| match attr.parse_meta() {
             Ok(Meta::Word(name)) => name == attribute_name,
             _ => false,
         })
     });
-    let field = fields.next();
-    if fields.next().is_some() {
-        panic!("Can't set more than one {} key", attribute_name);
+    let field = matches.next();
+    if let Some(extra) = matches.next() {
+        return Err(syn::Error::new_spanned(
+            &extra,
+            format!("Can't set more than one {} key", attribute_name),
+        ));
     }
-    field
+    Ok(field)
 }
 
 /// keys.insert(
 ///   "field_name", to_attribute_value(field)
 /// )
-fn get_key_inserter(field_name: &Option<Ident>) -> impl ToTokens {
+///
+/// honors `#[dynomite(rename = "...")]`, so a renamed hash/range field's key
+/// lines up with the attribute name `get_to_attribute_map_function` writes it
+/// under
+fn get_key_inserter(field: &Field) -> syn::Result<TokenStream2> {
     let to_attribute_value = quote!(::dynomite::Attribute::into_attr);
-    field_name
-        .as_ref()
-        .map(|field_name| {
-            quote! {
-                keys.insert(
-                    stringify!(#field_name).to_string(),
-                    #to_attribute_value(self.#field_name.clone())
-                );
-            }
+    let options = field_options(field)?;
+    let field_name = &field.ident;
+    let key = field_key_tokens(field_name, &options);
+    Ok(quote! {
+        keys.insert(
+            #key.to_string(),
+            #to_attribute_value(self.#field_name.clone())
+        );
+    })
+}
+
+/// strips the attributes dynomite special-cases for key/index derivation
+/// (`#[hash]`, `#[range]`, `#[gsi(...)]`, `#[lsi(...)]`) from a field clone
+/// destined for a derived key struct, so they don't duplicate or recurse,
+/// while leaving everything else -- notably `#[dynomite(rename = "...")]` --
+/// intact so the derived struct keeps looking the field up under the same
+/// DynamoDB attribute name as the original item
+fn non_key_attrs(attrs: Vec<Attribute>) -> Vec<Attribute> {
+    attrs
+        .into_iter()
+        .filter(|attr| {
+            let ident = match attr.parse_meta() {
+                Ok(Meta::Word(ident)) => ident,
+                Ok(Meta::List(list)) => list.ident,
+                Ok(Meta::NameValue(nv)) => nv.ident,
+                Err(_) => return true,
+            };
+            !(ident == "hash" || ident == "range" || ident == "gsi" || ident == "lsi")
         })
-        .unwrap_or(quote!())
+        .collect()
 }
 
 /// #[derive](Item, Debug, Clone, PartialEq)
@@ -379,22 +736,22 @@ fn get_key_struct(
     vis: &Visibility,
     name: &Ident,
     fields: &[Field],
-) -> impl ToTokens {
+) -> syn::Result<TokenStream2> {
     // fixme: this `Span` ref is the only dependency we have on the proc_macro2 crate
     // is this really needed?
     let name = Ident::new(&format!("{}Key", name), Span::call_site());
 
-    let hash_key = field_with_attribute(&fields, "hash");
-    let range_key = field_with_attribute(&fields, "range")
+    let hash_key = field_with_attribute(fields, "hash")?;
+    let range_key = field_with_attribute(fields, "range")?
         .map(|mut range_key| {
-            range_key.attrs = vec![];
+            range_key.attrs = non_key_attrs(range_key.attrs);
             quote! {#range_key}
         })
         .unwrap_or(quote!());
 
-    hash_key
+    Ok(hash_key
         .map(|mut hash_key| {
-            hash_key.attrs = vec![];
+            hash_key.attrs = non_key_attrs(hash_key.attrs);
             quote! {
                 #[derive(Item, Debug, Clone, PartialEq)]
                 #vis struct #name {
@@ -403,5 +760,441 @@ fn get_key_struct(
                 }
             }
         })
-        .unwrap_or(quote!())
+        .unwrap_or(quote!()))
+}
+
+#[derive(Clone, Copy)]
+enum IndexRole {
+    Hash,
+    Range,
+}
+
+/// a field tagged `#[gsi(name = "...", hash)]` or `#[lsi(name = "...", range)]`
+struct IndexField {
+    name: String,
+    role: IndexRole,
+    field: Field,
+}
+
+/// the hash/range fields collected for a single named secondary index
+#[derive(Default)]
+struct IndexSpec {
+    hash: Option<Field>,
+    range: Option<Field>,
+}
+
+fn index_fields(
+    fields: &[Field],
+    attribute_name: &str,
+) -> syn::Result<Vec<IndexField>> {
+    let mut found = Vec::new();
+
+    for field in fields {
+        for attr in &field.attrs {
+            let meta = match attr.parse_meta() {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+            let ident = match &meta {
+                Meta::Word(ident) => ident,
+                Meta::List(list) => &list.ident,
+                Meta::NameValue(nv) => &nv.ident,
+            };
+            if ident != attribute_name {
+                continue;
+            }
+            let list = match meta {
+                Meta::List(list) => list,
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        &other,
+                        format!("expected #[{}(...)] attribute list", attribute_name),
+                    ))
+                }
+            };
+
+            let mut name = None;
+            let mut role = None;
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.ident == "name" => match &nv.lit {
+                        Lit::Str(lit) => name = Some(lit.value()),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                &nv.lit,
+                                format!("#[{}(name = \"...\")] expects a string literal", attribute_name),
+                            ))
+                        }
+                    },
+                    NestedMeta::Meta(Meta::Word(ident)) if ident == "hash" => {
+                        role = Some(IndexRole::Hash);
+                    }
+                    NestedMeta::Meta(Meta::Word(ident)) if ident == "range" => {
+                        role = Some(IndexRole::Range);
+                    }
+                    other => {
+                        return Err(syn::Error::new_spanned(
+                            &other,
+                            format!("unrecognized #[{}(...)] argument", attribute_name),
+                        ))
+                    }
+                }
+            }
+
+            let name = name.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    field,
+                    format!("#[{}(...)] requires a `name = \"...\"` argument", attribute_name),
+                )
+            })?;
+            let role = role.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    field,
+                    format!("#[{}(...)] requires either `hash` or `range`", attribute_name),
+                )
+            })?;
+
+            found.push(IndexField {
+                name,
+                role,
+                field: field.clone(),
+            });
+        }
+    }
+
+    Ok(found)
+}
+
+/// collects `#[gsi]`/`#[lsi]` tagged fields into one `IndexSpec` per distinct
+/// index name, in first-seen order
+///
+/// index names are compared after `pascal_case`-ing, since that's what
+/// becomes part of the derived `{Name}{Index}Key` struct's identifier:
+/// `"by-email"` and `"by_email"` would otherwise silently collide into the
+/// same generated struct.
+fn collect_index_specs(fields: &[Field]) -> syn::Result<Vec<(String, IndexSpec)>> {
+    let mut specs: Vec<(String, IndexSpec)> = Vec::new();
+    let mut seen_pascal_names: Vec<(String, String)> = Vec::new();
+
+    for attribute_name in ["gsi", "lsi"].iter().copied() {
+        for entry in index_fields(fields, attribute_name)? {
+            let pascal_name = pascal_case(&entry.name);
+            if pascal_name.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &entry.field,
+                    format!(
+                        "index name \"{}\" doesn't contain any alphanumeric characters",
+                        entry.name
+                    ),
+                ));
+            }
+            match seen_pascal_names
+                .iter()
+                .find(|(pascal, _)| *pascal == pascal_name)
+            {
+                Some((_, original)) if original != &entry.name => {
+                    return Err(syn::Error::new_spanned(
+                        &entry.field,
+                        format!(
+                            "index name \"{}\" collides with \"{}\": both produce the same derived key struct name",
+                            entry.name, original
+                        ),
+                    ))
+                }
+                Some(_) => {}
+                None => seen_pascal_names.push((pascal_name, entry.name.clone())),
+            }
+
+            let spec = match specs.iter_mut().find(|(name, _)| name == &entry.name) {
+                Some((_, spec)) => spec,
+                None => {
+                    specs.push((entry.name.clone(), IndexSpec::default()));
+                    &mut specs.last_mut().unwrap().1
+                }
+            };
+            match entry.role {
+                IndexRole::Hash if spec.hash.is_some() => {
+                    return Err(syn::Error::new_spanned(
+                        &entry.field,
+                        format!("index `{}` already has a hash field", entry.name),
+                    ))
+                }
+                IndexRole::Hash => spec.hash = Some(entry.field),
+                IndexRole::Range if spec.range.is_some() => {
+                    return Err(syn::Error::new_spanned(
+                        &entry.field,
+                        format!("index `{}` already has a range field", entry.name),
+                    ))
+                }
+                IndexRole::Range => spec.range = Some(entry.field),
+            }
+        }
+    }
+
+    Ok(specs)
+}
+
+/// `by_email` -> `ByEmail`
+fn pascal_case(s: &str) -> String {
+    s.split(['_', '-'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// #[derive(Item, Debug, Clone, PartialEq)]
+/// pub struct NameByEmailKey {
+///    hash_key,
+///    range_key
+/// }
+fn get_index_key_struct(
+    vis: &Visibility,
+    name: &Ident,
+    index_name: &str,
+    spec: IndexSpec,
+) -> syn::Result<TokenStream2> {
+    let IndexSpec { hash, range } = spec;
+
+    let range_key = range
+        .map(|mut range_key| {
+            range_key.attrs = non_key_attrs(range_key.attrs);
+            range_key.attrs.push(parse_quote!(#[range]));
+            quote! {#range_key}
+        })
+        .unwrap_or(quote!());
+
+    let mut hash_key = hash.ok_or_else(|| {
+        syn::Error::new_spanned(
+            name,
+            format!(
+                "index `{}` is missing a #[hash] field (tag it with #[gsi(name = \"{}\", hash)] or #[lsi(name = \"{}\", hash)])",
+                index_name, index_name, index_name
+            ),
+        )
+    })?;
+    hash_key.attrs = non_key_attrs(hash_key.attrs);
+    hash_key.attrs.push(parse_quote!(#[hash]));
+
+    let key_name = Ident::new(&format!("{}{}Key", name, pascal_case(index_name)), Span::call_site());
+
+    Ok(quote! {
+        #[derive(Item, Debug, Clone, PartialEq)]
+        #vis struct #key_name {
+            #hash_key,
+            #range_key
+        }
+    })
+}
+
+fn get_index_key_structs(
+    vis: &Visibility,
+    name: &Ident,
+    fields: &[Field],
+) -> syn::Result<TokenStream2> {
+    let structs = collect_index_specs(fields)?
+        .into_iter()
+        .map(|(index_name, spec)| get_index_key_struct(vis, name, &index_name, spec))
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        #(#structs)*
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Ident {
+        Ident::new(name, Span::call_site())
+    }
+
+    /// parses a `struct Name { ... }` snippet and returns its named fields
+    fn parse_fields(src: &str) -> Vec<Field> {
+        let ast: DeriveInput = syn::parse_str(src).expect("failed to parse test struct");
+        match ast.data {
+            Struct(DataStruct {
+                fields: Fields::Named(named),
+                ..
+            }) => named.named.into_iter().collect(),
+            _ => panic!("expected a struct with named fields"),
+        }
+    }
+
+    #[test]
+    fn attribute_unit_variants_round_trip_via_s() {
+        let ast: DeriveInput = syn::parse_str("enum Color { Red, Green }").unwrap();
+        let tokens = expand_attribute(ast).unwrap().to_string();
+        assert!(tokens.contains("Red"));
+        assert!(tokens.contains("Green"));
+        assert!(tokens.contains("value . s"));
+    }
+
+    #[test]
+    fn attribute_data_variants_round_trip_via_map() {
+        let ast: DeriveInput =
+            syn::parse_str("enum Shape { Circle(f64), Rect { w: f64, h: f64 } }").unwrap();
+        let tokens = expand_attribute(ast).unwrap().to_string();
+        assert!(tokens.contains("Circle"));
+        assert!(tokens.contains("Rect"));
+        assert!(tokens.contains("HashMap"));
+    }
+
+    #[test]
+    fn attribute_newtype_variant_with_multiple_fields_errors() {
+        let ast: DeriveInput = syn::parse_str("enum Bad { Pair(u8, u8) }").unwrap();
+        let err = expand_attribute(ast).unwrap_err();
+        assert!(err.to_string().contains("single field"));
+    }
+
+    #[test]
+    fn attribute_on_non_enum_errors() {
+        let ast: DeriveInput = syn::parse_str("struct NotAnEnum;").unwrap();
+        let err = expand_attribute(ast).unwrap_err();
+        assert!(err.to_string().contains("enum types"));
+    }
+
+    #[test]
+    fn item_requires_named_fields() {
+        let ast: DeriveInput = syn::parse_str("struct Tuple(u8);").unwrap();
+        let err = expand_item(ast).unwrap_err();
+        assert!(err.to_string().contains("named fields"));
+    }
+
+    #[test]
+    fn item_duplicate_hash_errors() {
+        let ast: DeriveInput =
+            syn::parse_str("struct Bad { #[hash] a: String, #[hash] b: String }").unwrap();
+        let err = expand_item(ast).unwrap_err();
+        assert!(err.to_string().contains("more than one hash"));
+    }
+
+    #[test]
+    fn dynomite_skip_without_default_errors() {
+        let fields = parse_fields("struct S { #[dynomite(skip)] a: String }");
+        let err = field_options(&fields[0]).unwrap_err();
+        assert!(err.to_string().contains("requires #[dynomite(default)]"));
+    }
+
+    #[test]
+    fn dynomite_rename_is_parsed() {
+        let fields = parse_fields(r#"struct S { #[dynomite(rename = "other")] a: String }"#);
+        let options = field_options(&fields[0]).unwrap();
+        assert_eq!(options.rename, Some("other".to_string()));
+    }
+
+    #[test]
+    fn key_struct_preserves_rename_on_hash_field() {
+        let fields = parse_fields(
+            r#"struct Person { #[hash] #[dynomite(rename = "email_address")] email: String }"#,
+        );
+        let tokens = get_key_struct(&Visibility::Inherited, &ident("Person"), &fields)
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("email_address"));
+    }
+
+    #[test]
+    fn item_key_preserves_rename_on_hash_field() {
+        let fields = parse_fields(
+            r#"struct Person { #[hash] #[dynomite(rename = "email_address")] email: String }"#,
+        );
+        let tokens = get_item_trait(&ident("Person"), &fields)
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("email_address"));
+    }
+
+    #[test]
+    fn option_fields_are_detected() {
+        let fields = parse_fields("struct S { a: Option<String>, b: String }");
+        assert!(is_option_type(&fields[0].ty));
+        assert!(!is_option_type(&fields[1].ty));
+    }
+
+    /// `IndexField`/`Field` don't implement `Debug`, so `Result::unwrap_err` isn't
+    /// available on `index_fields`'s return type -- unwrap it by hand instead
+    fn expect_index_fields_err(result: syn::Result<Vec<IndexField>>) -> syn::Error {
+        match result {
+            Ok(_) => panic!("expected index_fields to error"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn malformed_gsi_attribute_errors() {
+        let fields = parse_fields("struct S { #[gsi] a: String }");
+        let err = expect_index_fields_err(index_fields(&fields, "gsi"));
+        assert!(err.to_string().contains("attribute list"));
+    }
+
+    #[test]
+    fn gsi_without_name_errors() {
+        let fields = parse_fields("struct S { #[gsi(hash)] a: String }");
+        let err = expect_index_fields_err(index_fields(&fields, "gsi"));
+        assert!(err.to_string().contains("requires a `name"));
+    }
+
+    /// `IndexSpec` holds a `syn::Field` and doesn't implement `Debug` either,
+    /// so `collect_index_specs`'s error path needs the same by-hand unwrap
+    fn expect_index_specs_err(result: syn::Result<Vec<(String, IndexSpec)>>) -> syn::Error {
+        match result {
+            Ok(_) => panic!("expected collect_index_specs to error"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn differently_spelled_index_names_collide_after_pascal_case() {
+        let fields = parse_fields(
+            "struct S { \
+             #[gsi(name = \"by-email\", hash)] a: String, \
+             #[gsi(name = \"by_email\", range)] b: String \
+             }",
+        );
+        let err = expect_index_specs_err(collect_index_specs(&fields));
+        assert!(err.to_string().contains("collides with"));
+    }
+
+    #[test]
+    fn empty_index_name_errors() {
+        let fields = parse_fields(r#"struct S { #[gsi(name = "", hash)] a: String }"#);
+        let err = expect_index_specs_err(collect_index_specs(&fields));
+        assert!(err.to_string().contains("alphanumeric"));
+    }
+
+    #[test]
+    fn index_key_struct_preserves_rename_on_hash_field() {
+        let fields = parse_fields(
+            r#"struct Person {
+                #[gsi(name = "by_email", hash)]
+                #[dynomite(rename = "email_address")]
+                email: String
+            }"#,
+        );
+        let tokens = get_index_key_structs(&Visibility::Inherited, &ident("Person"), &fields)
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("PersonByEmailKey"));
+        assert!(tokens.contains("email_address"));
+    }
+
+    #[test]
+    fn index_key_struct_does_not_duplicate_gsi_attribute() {
+        let fields =
+            parse_fields(r#"struct Person { #[gsi(name = "by_email", hash)] email: String }"#);
+        let tokens = get_index_key_structs(&Visibility::Inherited, &ident("Person"), &fields)
+            .unwrap()
+            .to_string();
+        // the generated key struct's field should carry a fresh #[hash], not the
+        // original #[gsi(...)] -- otherwise deriving Item on it would try to
+        // expand the same index all over again
+        assert!(!tokens.contains("gsi"));
+    }
 }